@@ -1,21 +1,96 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright 2025 Echo contributors
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use argon2::{password_hash::{rand_core::OsRng, PasswordHasher, PasswordVerifier, SaltString}, Argon2};
-use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::{get, post}, Json, Router};
+use argon2::{password_hash::{rand_core::{OsRng, RngCore}, PasswordHasher, PasswordVerifier, SaltString}, Argon2};
+use axum::{
+    extract::{FromRequestParts, State},
+    http::{request::Parts, header::AUTHORIZATION, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use dotenvy::dotenv;
 use jsonwebtoken as jwt;
+use rsa::{pkcs8::DecodePublicKey, traits::PublicKeyParts, RsaPublicKey};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
-use tracing::{error, info};
+use time::Duration as CookieDuration;
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+#[derive(Clone)]
+struct AsymKey {
+    kid: String,
+    alg: jwt::Algorithm,
+    encoding: jwt::EncodingKey,
+    decoding: jwt::DecodingKey,
+    // Kept around (rather than just the parsed key) so the JWKS endpoint can
+    // re-derive n/e without storing a second parsed representation.
+    public_pem: Vec<u8>,
+}
+
 #[derive(Clone)]
 struct AppState {
     // Vec of (kid, secret bytes). First element is used as default when no active_kid specified.
     secrets: Vec<(String, Vec<u8>)>,
     active_kid: String,
+    // Asymmetric (RS256/ES256) signing keys, keyed the same way as `secrets`.
+    asym_keys: Vec<AsymKey>,
+    active_asym_kid: Option<String>,
+    // Stamped into every issued token's `iss`/`aud` claims and enforced on verify.
+    issuer: String,
+    audience: String,
+    // Opaque refresh tokens for the OAuth grant, keyed by SHA-256 hash of the
+    // token so the raw value is never stored at rest.
+    refresh_store: Arc<Mutex<HashMap<String, RefreshEntry>>>,
+    // Tombstones of hashes that were valid and have since been rotated away,
+    // kept until their original expiry. A hash showing up here on a later
+    // `/oauth/token` refresh call means the rotated-out token was replayed
+    // (theft), so the whole `family` it belongs to is revoked.
+    used_refresh: Arc<Mutex<HashMap<String, UsedRefresh>>>,
+    // Name of the cookie `AuthUser` reads from and `issue_token` can write to.
+    cookie_name: String,
+    // Target Argon2id cost parameters; hashes weaker than this are flagged for
+    // rehashing on successful verify.
+    argon2: Argon2<'static>,
+    // sub -> stored Argon2 PHC hash, looked up server-side for the OAuth
+    // password grant. A real deployment backs this with a user database;
+    // this in-memory map is the wiring point for that lookup.
+    users: Arc<HashMap<String, String>>,
+}
+
+#[derive(Clone)]
+struct RefreshEntry {
+    sub: String,
+    expires_at: u64,
+    // All refresh tokens descended from the same original login share a
+    // `family` id, so reuse of any one of them can revoke the rest.
+    family: u64,
+}
+
+#[derive(Clone, Copy)]
+struct UsedRefresh {
+    family: u64,
+    expires_at: u64,
+}
+
+/// What a token is for. Distinct purposes get distinct `typ` claims so, e.g.,
+/// a password-reset token can't be replayed as a login token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TokenPurpose {
+    Login,
+    Refresh,
+    EmailVerify,
+    PasswordReset,
+    Admin,
 }
 
 #[derive(Debug, Error)]
@@ -44,16 +119,67 @@ struct HashRes { hash: String }
 #[derive(Deserialize)]
 struct VerifyReq { password: String, hash: String }
 #[derive(Serialize)]
-struct VerifyRes { valid: bool }
+struct VerifyRes {
+    valid: bool,
+    // Set when `hash` was produced with weaker parameters than the service's
+    // current target, so the caller can store `new_hash` in place of `hash`.
+    needs_rehash: bool,
+    new_hash: Option<String>,
+}
 
 #[derive(Deserialize)]
-struct TokenReq { sub: String, exp_seconds: Option<u64> }
+struct TokenReq {
+    sub: String,
+    exp_seconds: Option<u64>,
+    // When set, sign with the active asymmetric (RS256/ES256) key instead of
+    // the default HS256 shared secret.
+    asym: Option<bool>,
+    purpose: TokenPurpose,
+    scopes: Option<Vec<String>>,
+    // When set, also return the token as an HttpOnly/Secure cookie for
+    // browser-based PWA clients that prefer cookie sessions over manual storage.
+    set_cookie: Option<bool>,
+}
 #[derive(Serialize, Deserialize)]
-struct Claims { sub: String, exp: u64 }
+struct Claims {
+    sub: String,
+    exp: u64,
+    iat: u64,
+    nbf: u64,
+    iss: String,
+    aud: String,
+    typ: TokenPurpose,
+    scope: Option<String>,
+}
 #[derive(Serialize)]
 struct TokenRes { token: String }
 #[derive(Deserialize)]
-struct VerifyTokenReq { token: String }
+struct VerifyTokenReq {
+    token: String,
+    expected_purpose: Option<TokenPurpose>,
+}
+
+#[derive(Deserialize)]
+struct OAuthTokenReq {
+    grant_type: String,
+    // grant_type=password
+    sub: Option<String>,
+    password: Option<String>,
+    // grant_type=refresh_token
+    refresh_token: Option<String>,
+}
+#[derive(Serialize)]
+struct OAuthTokenRes {
+    access_token: String,
+    refresh_token: String,
+    token_type: &'static str,
+    expires_in: u64,
+}
+#[derive(Deserialize)]
+struct OAuthRevokeReq { refresh_token: String }
+
+const OAUTH_ACCESS_TOKEN_SECONDS: u64 = 900;
+const OAUTH_REFRESH_TOKEN_SECONDS: u64 = 30 * 24 * 3600;
 
 #[tokio::main]
 async fn main() {
@@ -91,7 +217,49 @@ async fn main() {
             (vec![("default".to_string(), secret.into_bytes())], "default".to_string())
         }
     };
-    let state = AppState { secrets, active_kid };
+    // Asymmetric (RS256/ES256) signing keys, loaded the same way as JWT_SECRETS:
+    // JWT_ASYM_KEYS="kid1:rs256:/path/to/priv1.pem:/path/to/pub1.pem,kid2:es256:/path/priv2.pem:/path/pub2.pem"
+    // JWT_ASYM_ACTIVE_KID="kid1"
+    let (asym_keys, active_asym_kid) = load_asym_keys();
+
+    let issuer = std::env::var("JWT_ISSUER").unwrap_or_else(|_| "echo-auth".to_string());
+    let audience = std::env::var("JWT_AUDIENCE").unwrap_or_else(|_| "echo".to_string());
+
+    let refresh_store: Arc<Mutex<HashMap<String, RefreshEntry>>> = Arc::new(Mutex::new(HashMap::new()));
+    let used_refresh: Arc<Mutex<HashMap<String, UsedRefresh>>> = Arc::new(Mutex::new(HashMap::new()));
+    let cookie_name = std::env::var("JWT_COOKIE_NAME").unwrap_or_else(|_| "echo_token".to_string());
+
+    // Argon2id cost parameters (OWASP-recommended defaults), overridable so the
+    // target can be raised over time without a code change.
+    let argon2_mem_kib: u32 = std::env::var("ARGON2_MEMORY_KIB").ok().and_then(|s| s.parse().ok()).unwrap_or(19_456);
+    let argon2_iterations: u32 = std::env::var("ARGON2_ITERATIONS").ok().and_then(|s| s.parse().ok()).unwrap_or(2);
+    let argon2_parallelism: u32 = std::env::var("ARGON2_PARALLELISM").ok().and_then(|s| s.parse().ok()).unwrap_or(1);
+    let argon2_params = argon2::Params::new(argon2_mem_kib, argon2_iterations, argon2_parallelism, None)
+        .expect("valid Argon2 parameters");
+    let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, argon2_params);
+
+    // Backing store for the OAuth password grant: OAUTH_USERS="sub1:<phc-hash>,sub2:<phc-hash>".
+    // Placeholder for a real user database lookup.
+    let users: Arc<HashMap<String, String>> = Arc::new(
+        std::env::var("OAUTH_USERS").ok().map(|spec| {
+            spec.split(',').filter_map(|part| part.split_once(':')).map(|(sub, hash)| (sub.trim().to_string(), hash.trim().to_string())).collect()
+        }).unwrap_or_default()
+    );
+
+    let state = AppState { secrets, active_kid, asym_keys, active_asym_kid, issuer, audience, refresh_store: refresh_store.clone(), used_refresh: used_refresh.clone(), cookie_name, argon2, users };
+
+    // Periodically sweep expired refresh tokens (and their used-token
+    // tombstones) so the in-memory stores don't grow unbounded for clients
+    // that never call /oauth/revoke.
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
+            refresh_store.lock().unwrap().retain(|_, entry| entry.expires_at > now);
+            used_refresh.lock().unwrap().retain(|_, used| used.expires_at > now);
+        }
+    });
 
     let app = Router::new()
         .route("/health", get(|| async { "ok" }))
@@ -99,6 +267,10 @@ async fn main() {
         .route("/verify", post(verify_password))
         .route("/token", post(issue_token))
         .route("/token/verify", post(verify_token))
+        .route("/.well-known/jwks.json", get(jwks))
+        .route("/oauth/token", post(oauth_token))
+        .route("/oauth/revoke", post(oauth_revoke))
+        .route("/me", get(me))
         .with_state(state);
 
     let port: u16 = std::env::var("PORT").ok().and_then(|s| s.parse().ok()).unwrap_or(8080);
@@ -108,29 +280,142 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
-async fn hash_password(Json(req): Json<HashReq>) -> Result<Json<HashRes>, ApiError> {
+// Parses JWT_ASYM_KEYS="kid:alg:private_pem_path:public_pem_path,..." plus
+// JWT_ASYM_ACTIVE_KID, loading each PEM pair from disk. `alg` is one of
+// "rs256" or "es256" (case-insensitive). Unreadable/unparseable entries are
+// skipped with a logged error rather than failing startup, since asymmetric
+// signing is opt-in.
+fn load_asym_keys() -> (Vec<AsymKey>, Option<String>) {
+    let Ok(spec) = std::env::var("JWT_ASYM_KEYS") else { return (vec![], None); };
+    let mut keys = vec![];
+    for part in spec.split(',') {
+        let fields: Vec<&str> = part.splitn(4, ':').collect();
+        let [kid, alg, priv_path, pub_path] = fields[..] else {
+            error!(part, "malformed JWT_ASYM_KEYS entry, expected kid:alg:priv_path:pub_path");
+            continue;
+        };
+        let alg = match alg.to_ascii_lowercase().as_str() {
+            "rs256" => jwt::Algorithm::RS256,
+            "es256" => jwt::Algorithm::ES256,
+            other => { error!(alg = other, "unsupported asymmetric alg"); continue; }
+        };
+        let (Ok(private_pem), Ok(public_pem)) = (std::fs::read(priv_path), std::fs::read(pub_path)) else {
+            error!(kid, "failed to read asymmetric key PEM files");
+            continue;
+        };
+        let (encoding, decoding) = match alg {
+            jwt::Algorithm::RS256 => (
+                jwt::EncodingKey::from_rsa_pem(&private_pem),
+                jwt::DecodingKey::from_rsa_pem(&public_pem),
+            ),
+            jwt::Algorithm::ES256 => (
+                jwt::EncodingKey::from_ec_pem(&private_pem),
+                jwt::DecodingKey::from_ec_pem(&public_pem),
+            ),
+            _ => unreachable!(),
+        };
+        match (encoding, decoding) {
+            (Ok(encoding), Ok(decoding)) => {
+                keys.push(AsymKey { kid: kid.to_string(), alg, encoding, decoding, public_pem });
+            }
+            _ => error!(kid, "failed to parse asymmetric key PEM"),
+        }
+    }
+    let active = std::env::var("JWT_ASYM_ACTIVE_KID").ok().filter(|v| !v.is_empty())
+        .or_else(|| keys.first().map(|k| k.kid.clone()));
+    (keys, active)
+}
+
+// Builds the `n`/`e` JWK fields for an RSA public key in base64url (no padding).
+fn rsa_jwk_params(public_pem: &[u8]) -> Result<(String, String), ()> {
+    let pem = std::str::from_utf8(public_pem).map_err(|_| ())?;
+    let key = RsaPublicKey::from_public_key_pem(pem).map_err(|_| ())?;
+    let n = URL_SAFE_NO_PAD.encode(key.n().to_bytes_be());
+    let e = URL_SAFE_NO_PAD.encode(key.e().to_bytes_be());
+    Ok((n, e))
+}
+
+async fn jwks(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let keys: Vec<serde_json::Value> = state.asym_keys.iter()
+        .filter(|k| k.alg == jwt::Algorithm::RS256)
+        .filter_map(|k| {
+            let (n, e) = rsa_jwk_params(&k.public_pem).ok()?;
+            Some(serde_json::json!({
+                "kty": "RSA",
+                "use": "sig",
+                "kid": k.kid,
+                "alg": "RS256",
+                "n": n,
+                "e": e,
+            }))
+        })
+        .collect();
+    Json(serde_json::json!({ "keys": keys }))
+}
+
+async fn hash_password(State(state): State<AppState>, Json(req): Json<HashReq>) -> Result<Json<HashRes>, ApiError> {
     if req.password.is_empty() { return Err(ApiError::BadRequest); }
     let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let hash = argon2
+    let hash = state.argon2
         .hash_password(req.password.as_bytes(), &salt)
         .map_err(|_| ApiError::Internal)?
         .to_string();
     Ok(Json(HashRes { hash }))
 }
 
-async fn verify_password(Json(req): Json<VerifyReq>) -> Result<Json<VerifyRes>, ApiError> {
+async fn verify_password(State(state): State<AppState>, Json(req): Json<VerifyReq>) -> Result<Json<VerifyRes>, ApiError> {
     if req.password.is_empty() || req.hash.is_empty() { return Err(ApiError::BadRequest); }
     let parsed = password_hash::PasswordHash::new(&req.hash).map_err(|_| ApiError::BadRequest)?;
-    let ok = Argon2::default().verify_password(req.password.as_bytes(), &parsed).is_ok();
-    Ok(Json(VerifyRes { valid: ok }))
+    if state.argon2.verify_password(req.password.as_bytes(), &parsed).is_err() {
+        return Ok(Json(VerifyRes { valid: false, needs_rehash: false, new_hash: None }));
+    }
+
+    let target = state.argon2.params();
+    let weaker = argon2::Params::try_from(&parsed)
+        .map(|stored| stored.m_cost() < target.m_cost() || stored.t_cost() < target.t_cost() || stored.p_cost() < target.p_cost())
+        .unwrap_or(false);
+
+    let new_hash = if weaker {
+        let salt = SaltString::generate(&mut OsRng);
+        Some(state.argon2.hash_password(req.password.as_bytes(), &salt).map_err(|_| ApiError::Internal)?.to_string())
+    } else {
+        None
+    };
+
+    Ok(Json(VerifyRes { valid: true, needs_rehash: weaker, new_hash }))
 }
 
-async fn issue_token(State(state): State<AppState>, Json(req): Json<TokenReq>) -> Result<Json<TokenRes>, ApiError> {
-    if req.sub.is_empty() { return Err(ApiError::BadRequest); }
+// Builds and signs a JWT for `sub`/`purpose`, used by both the `/token` handler
+// and the OAuth access-token grants. `asym` selects the active asymmetric key
+// over the default HS256 shared secret.
+fn mint_token(
+    state: &AppState,
+    sub: String,
+    purpose: TokenPurpose,
+    exp_seconds: u64,
+    scopes: Option<Vec<String>>,
+    asym: bool,
+) -> Result<String, ApiError> {
     let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_err(|_| ApiError::Internal)?.as_secs();
-    let exp = now + req.exp_seconds.unwrap_or(3600);
-    let claims = Claims { sub: req.sub, exp };
+    let claims = Claims {
+        sub,
+        exp: now + exp_seconds,
+        iat: now,
+        nbf: now,
+        iss: state.issuer.clone(),
+        aud: state.audience.clone(),
+        typ: purpose,
+        scope: scopes.map(|s| s.join(" ")),
+    };
+
+    if asym {
+        let active_kid = state.active_asym_kid.as_deref().ok_or(ApiError::Internal)?;
+        let asym_key = state.asym_keys.iter().find(|k| k.kid == active_kid).ok_or(ApiError::Internal)?;
+        let mut header = jwt::Header::new(asym_key.alg);
+        header.kid = Some(asym_key.kid.clone());
+        return jwt::encode(&header, &claims, &asym_key.encoding)
+            .map_err(|e| { error!(?e, "jwt encode error"); ApiError::Internal });
+    }
 
     let mut header = jwt::Header { alg: jwt::Algorithm::HS256, ..Default::default() };
     header.kid = Some(state.active_kid.clone());
@@ -143,15 +428,80 @@ async fn issue_token(State(state): State<AppState>, Json(req): Json<TokenReq>) -
         .unwrap_or_else(|| state.secrets.first().map(|(_, s)| s.as_slice()).unwrap_or(&[]));
     let key = jwt::EncodingKey::from_secret(secret_bytes);
 
-    let token = jwt::encode(&header, &claims, &key).map_err(|e| { error!(?e, "jwt encode error"); ApiError::Internal })?;
-    Ok(Json(TokenRes { token }))
+    jwt::encode(&header, &claims, &key).map_err(|e| { error!(?e, "jwt encode error"); ApiError::Internal })
 }
 
-async fn verify_token(State(state): State<AppState>, Json(req): Json<VerifyTokenReq>) -> Result<Json<Claims>, ApiError> {
-    if req.token.is_empty() { return Err(ApiError::BadRequest); }
-    let validation = jwt::Validation::new(jwt::Algorithm::HS256);
+async fn issue_token(State(state): State<AppState>, jar: CookieJar, Json(req): Json<TokenReq>) -> Result<(CookieJar, Json<TokenRes>), ApiError> {
+    if req.sub.is_empty() { return Err(ApiError::BadRequest); }
+    let exp_seconds = req.exp_seconds.unwrap_or(3600);
+    let token = mint_token(&state, req.sub, req.purpose, exp_seconds, req.scopes, req.asym.unwrap_or(false))?;
+
+    let jar = if req.set_cookie.unwrap_or(false) {
+        let cookie = Cookie::build((state.cookie_name.clone(), token.clone()))
+            .http_only(true)
+            .secure(true)
+            .same_site(SameSite::Lax)
+            .path("/")
+            .max_age(CookieDuration::seconds(exp_seconds as i64))
+            .build();
+        jar.add(cookie)
+    } else {
+        jar
+    };
+
+    Ok((jar, Json(TokenRes { token })))
+}
+
+// Validation shared by both the symmetric and asymmetric decode paths: enforce
+// the configured issuer/audience on top of jsonwebtoken's default exp check.
+fn build_validation(alg: jwt::Algorithm, state: &AppState) -> jwt::Validation {
+    let mut validation = jwt::Validation::new(alg);
+    validation.set_issuer(std::slice::from_ref(&state.issuer));
+    validation.set_audience(std::slice::from_ref(&state.audience));
+    validation.validate_nbf = true;
+    validation
+}
+
+fn check_claims(claims: Claims, expected_purpose: Option<TokenPurpose>) -> Result<Claims, ApiError> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_err(|_| ApiError::Internal)?.as_secs();
+    if claims.exp < now { return Err(ApiError::Unauthorized); }
+    if let Some(expected) = expected_purpose {
+        if claims.typ != expected { return Err(ApiError::Unauthorized); }
+    }
+    Ok(claims)
+}
+
+// Shared by `/token/verify` and the `AuthUser` extractor: picks the algorithm
+// from the token header, selects the matching key(s) by `kid` (falling back to
+// trying all keys for that algorithm when no `kid` is present), and enforces
+// iss/aud/exp/nbf plus an optional required purpose.
+fn decode_token(token: &str, state: &AppState, expected_purpose: Option<TokenPurpose>) -> Result<Claims, ApiError> {
+    let header = jwt::decode_header(token).map_err(|_| ApiError::Unauthorized)?;
+
+    if header.alg == jwt::Algorithm::RS256 || header.alg == jwt::Algorithm::ES256 {
+        let validation = build_validation(header.alg, state);
+        let try_order: Vec<&jwt::DecodingKey> = if let Some(kid) = &header.kid {
+            if let Some(k) = state.asym_keys.iter().find(|k| &k.kid == kid) {
+                vec![&k.decoding]
+            } else {
+                state.asym_keys.iter().filter(|k| k.alg == header.alg).map(|k| &k.decoding).collect()
+            }
+        } else {
+            state.asym_keys.iter().filter(|k| k.alg == header.alg).map(|k| &k.decoding).collect()
+        };
+        let mut last_err: Option<jwt::errors::Error> = None;
+        for key in try_order {
+            match jwt::decode::<Claims>(token, key, &validation) {
+                Ok(data) => return check_claims(data.claims, expected_purpose),
+                Err(e) => { last_err = Some(e); }
+            }
+        }
+        error!(?last_err, "jwt verify failed");
+        return Err(ApiError::Unauthorized);
+    }
+
+    let validation = build_validation(jwt::Algorithm::HS256, state);
     // Try to use KID if present, else try all secrets
-    let header = jwt::decode_header(&req.token).map_err(|_| ApiError::Unauthorized)?;
     let try_order: Vec<&[u8]> = if let Some(kid) = header.kid {
         if let Some((_, sec)) = state.secrets.iter().find(|(k, _)| *k == kid) {
             vec![sec.as_slice()]
@@ -164,17 +514,134 @@ async fn verify_token(State(state): State<AppState>, Json(req): Json<VerifyToken
     let mut last_err: Option<jwt::errors::Error> = None;
     for sec in try_order {
         let key = jwt::DecodingKey::from_secret(sec);
-        match jwt::decode::<Claims>(&req.token, &key, &validation) {
-            Ok(data) => {
-                let claims = data.claims;
-                // Additional exp check (Validation should already cover it if set)
-                let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_err(|_| ApiError::Internal)?.as_secs();
-                if claims.exp < now { return Err(ApiError::Unauthorized); }
-                return Ok(Json(claims));
-            },
+        match jwt::decode::<Claims>(token, &key, &validation) {
+            Ok(data) => return check_claims(data.claims, expected_purpose),
             Err(e) => { last_err = Some(e); }
         }
     }
     error!(?last_err, "jwt verify failed");
     Err(ApiError::Unauthorized)
 }
+
+async fn verify_token(State(state): State<AppState>, Json(req): Json<VerifyTokenReq>) -> Result<Json<Claims>, ApiError> {
+    if req.token.is_empty() { return Err(ApiError::BadRequest); }
+    decode_token(&req.token, &state, req.expected_purpose).map(Json)
+}
+
+// Extracts and verifies a bearer token from either the `Authorization` header
+// or the configured session cookie, yielding the caller's `Claims`. Any route
+// that adds `AuthUser` as a handler argument becomes auth-gated.
+struct AuthUser(Claims);
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let token = if let Some(value) = parts.headers.get(AUTHORIZATION) {
+            let value = value.to_str().map_err(|_| ApiError::Unauthorized)?;
+            value.strip_prefix("Bearer ").ok_or(ApiError::Unauthorized)?.to_string()
+        } else {
+            let jar = CookieJar::from_headers(&parts.headers);
+            jar.get(&state.cookie_name).map(|c| c.value().to_string()).ok_or(ApiError::Unauthorized)?
+        };
+        // Only a `login`-purpose token authenticates a caller here — a
+        // password_reset/email_verify/admin token must not pass this generic
+        // guard (see the purpose isolation added for request #2).
+        let claims = decode_token(&token, state, Some(TokenPurpose::Login))?;
+        Ok(AuthUser(claims))
+    }
+}
+
+async fn me(AuthUser(claims): AuthUser) -> Json<Claims> {
+    Json(claims)
+}
+
+// 256 bits of OsRng entropy, base64url-encoded, used as the opaque refresh token.
+fn generate_refresh_token() -> String {
+    let mut buf = [0u8; 32];
+    OsRng.fill_bytes(&mut buf);
+    URL_SAFE_NO_PAD.encode(buf)
+}
+
+fn hash_refresh_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+// Identifies the chain of refresh tokens descended from one original login,
+// so reuse of any token in the chain can revoke the whole chain at once.
+fn generate_family_id() -> u64 {
+    let mut buf = [0u8; 8];
+    OsRng.fill_bytes(&mut buf);
+    u64::from_le_bytes(buf)
+}
+
+fn issue_refresh_token(state: &AppState, sub: String, family: u64) -> Result<String, ApiError> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_err(|_| ApiError::Internal)?.as_secs();
+    let token = generate_refresh_token();
+    let entry = RefreshEntry { sub, expires_at: now + OAUTH_REFRESH_TOKEN_SECONDS, family };
+    state.refresh_store.lock().unwrap().insert(hash_refresh_token(&token), entry);
+    Ok(token)
+}
+
+async fn oauth_token(State(state): State<AppState>, Json(req): Json<OAuthTokenReq>) -> Result<Json<OAuthTokenRes>, ApiError> {
+    let (sub, family) = match req.grant_type.as_str() {
+        "password" => {
+            let (sub, password) = match (req.sub, req.password) {
+                (Some(sub), Some(password)) if !sub.is_empty() && !password.is_empty() => (sub, password),
+                _ => return Err(ApiError::BadRequest),
+            };
+            // The stored hash is looked up server-side, never taken from the
+            // request, so a caller can't authenticate as an arbitrary `sub`
+            // by supplying their own matching password/hash pair.
+            let hash = state.users.get(&sub).ok_or(ApiError::Unauthorized)?;
+            let parsed = password_hash::PasswordHash::new(hash).map_err(|_| ApiError::Internal)?;
+            if state.argon2.verify_password(password.as_bytes(), &parsed).is_err() {
+                return Err(ApiError::Unauthorized);
+            }
+            (sub, generate_family_id())
+        }
+        "refresh_token" => {
+            let presented = req.refresh_token.filter(|t| !t.is_empty()).ok_or(ApiError::BadRequest)?;
+            let hashed = hash_refresh_token(&presented);
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_err(|_| ApiError::Internal)?.as_secs();
+            // Rotation: the presented token is removed so it can't be used
+            // twice. On success it's tombstoned in `used_refresh` (rather than
+            // just dropped) so a later replay of this same rotated-away token
+            // is recognized as reuse/theft and revokes the whole token family,
+            // not merely rejected like an unrelated garbage/expired token.
+            let entry = state.refresh_store.lock().unwrap().remove(&hashed);
+            match entry {
+                Some(entry) if entry.expires_at > now => {
+                    state.used_refresh.lock().unwrap().insert(hashed, UsedRefresh { family: entry.family, expires_at: entry.expires_at });
+                    (entry.sub, entry.family)
+                }
+                Some(_) => { warn!("expired refresh token presented"); return Err(ApiError::Unauthorized); }
+                None => {
+                    if let Some(used) = state.used_refresh.lock().unwrap().get(&hashed).copied() {
+                        warn!(family = used.family, "refresh token reuse detected, revoking token family");
+                        state.refresh_store.lock().unwrap().retain(|_, e| e.family != used.family);
+                        return Err(ApiError::Unauthorized);
+                    }
+                    warn!("unknown refresh token presented");
+                    return Err(ApiError::Unauthorized);
+                }
+            }
+        }
+        _ => return Err(ApiError::BadRequest),
+    };
+
+    let access_token = mint_token(&state, sub.clone(), TokenPurpose::Login, OAUTH_ACCESS_TOKEN_SECONDS, None, false)?;
+    let refresh_token = issue_refresh_token(&state, sub, family)?;
+    Ok(Json(OAuthTokenRes {
+        access_token,
+        refresh_token,
+        token_type: "Bearer",
+        expires_in: OAUTH_ACCESS_TOKEN_SECONDS,
+    }))
+}
+
+async fn oauth_revoke(State(state): State<AppState>, Json(req): Json<OAuthRevokeReq>) -> StatusCode {
+    state.refresh_store.lock().unwrap().remove(&hash_refresh_token(&req.refresh_token));
+    StatusCode::OK
+}